@@ -1,69 +1,108 @@
-use crate::{ChainEntry, ChainPrefix, ChainSuffix, Datestamp, MarkovChain, TextSource, NGRAM_CNT};
+use crate::{
+    ChainEntry, ChainPrefix, ChainSuffix, Datestamp, ExtractedMessage, LogFormat, MarkovChain,
+    MessageDump, PlainText, TerminalDetection, TextSource,
+};
 use chrono::{Datelike, NaiveDateTime};
 use indexmap::IndexSet;
-use std::convert::TryInto;
 use std::iter::FromIterator;
 
-use vkopt_message_parser::reader::{fold_html, EventResult, MessageEvent};
+/// Default `chrono` format string `append_timestamped_text` looks for at the start of
+/// each line.
+pub const DEFAULT_TEXT_TIMESTAMP_FORMAT: &str = "%Y.%m.%d %H:%M:%S";
 
 pub trait ChainAppend {
-    fn append_text(&mut self, input_file: &str, source_names: Vec<String>, datestamp: Datestamp);
+    /// `order` is the number of preceding words each entry's prefix carries at most; every
+    /// shorter order down to 0 (the unigram, empty-prefix case) is recorded alongside it, so
+    /// generation can back off to it when the full context hasn't been seen.
+    fn append_text(
+        &mut self,
+        input_file: &str,
+        source_names: Vec<String>,
+        datestamp: Datestamp,
+        order: usize,
+    );
 
-    fn append_message_dump(&mut self, input_file: &str);
-}
+    /// Like `append_text`, but derives each line's `Datestamp` from a leading timestamp
+    /// matching `timestamp_format` instead of stamping the whole file with one date.
+    /// Lines without a parseable timestamp inherit the previous line's; n-grams never
+    /// span across lines.
+    fn append_timestamped_text(
+        &mut self,
+        input_file: &str,
+        source_names: Vec<String>,
+        timestamp_format: &str,
+        order: usize,
+    );
 
-#[derive(Default)]
-struct ExtractedMessage {
-    names: Vec<String>,
-    datestamp: Datestamp,
-    body: String,
+    fn append_message_dump(&mut self, input_file: &str, order: usize);
+
+    fn append_log<F: LogFormat>(&mut self, input_file: &str, format: F, order: usize);
 }
 
 impl ChainAppend for MarkovChain {
-    fn append_text(&mut self, input_file: &str, source_names: Vec<String>, datestamp: Datestamp) {
+    fn append_text(
+        &mut self,
+        input_file: &str,
+        source_names: Vec<String>,
+        datestamp: Datestamp,
+        order: usize,
+    ) {
+        self.append_log(
+            input_file,
+            PlainText {
+                source_names,
+                datestamp,
+            },
+            order,
+        );
+    }
+
+    fn append_timestamped_text(
+        &mut self,
+        input_file: &str,
+        source_names: Vec<String>,
+        timestamp_format: &str,
+        order: usize,
+    ) {
         let text = std::fs::read_to_string(input_file).unwrap();
         let source = source_by_names(&mut self.sources, source_names);
-        push_text_entries(&text, datestamp, &mut source.entries, &mut self.words, true);
-    }
 
-    fn append_message_dump(&mut self, input_file: &str) {
-        let last_msg = fold_html(
-            input_file,
-            Default::default(),
-            |mut msg: ExtractedMessage, event| match event {
-                MessageEvent::Start(0) => {
-                    if !msg.body.is_empty() {
-                        append_message(self, msg);
-                    }
-                    EventResult::Consumed(Default::default())
-                }
-                MessageEvent::FullNameExtracted(full_name) => {
-                    msg.names.push(full_name.to_owned());
-                    EventResult::Consumed(msg)
-                }
-                MessageEvent::ShortNameExtracted(short_name) => {
-                    msg.names.push(short_name.to_owned());
-                    EventResult::Consumed(msg)
-                }
-                MessageEvent::DateExtracted(date) => {
-                    let timestamp =
-                        NaiveDateTime::parse_from_str(date, "%Y.%m.%d %H:%M:%S").unwrap();
-                    msg.datestamp = Datestamp {
+        let mut datestamp = Datestamp::default();
+        for line in text.lines() {
+            let body = match NaiveDateTime::parse_and_remainder(line, timestamp_format) {
+                Ok((timestamp, remainder)) => {
+                    datestamp = Datestamp {
                         year: timestamp.year() as i16,
                         day: timestamp.ordinal() as u16,
                     };
-                    EventResult::Consumed(msg)
-                }
-                MessageEvent::BodyPartExtracted(body) => {
-                    msg.body.push_str(body);
-                    EventResult::Consumed(msg)
+                    remainder.trim_start()
                 }
-                _ => EventResult::Consumed(msg),
-            },
-        )
-        .unwrap();
-        if !last_msg.body.is_empty() {
-            append_message(self, last_msg);
+                Err(_) => line,
+            };
+            push_text_entries(
+                body,
+                datestamp,
+                &mut source.entries,
+                &mut self.words,
+                true,
+                order,
+            );
+        }
+    }
+
+    fn append_message_dump(&mut self, input_file: &str, order: usize) {
+        self.append_log(input_file, MessageDump, order);
+    }
+
+    fn append_log<F: LogFormat>(&mut self, input_file: &str, format: F, order: usize) {
+        let treat_ending_punctuation_as_terminal = matches!(
+            format.terminal_detection(),
+            TerminalDetection::EndingPunctuation
+        );
+        for message in format.parse(input_file) {
+            if !message.body.is_empty() {
+                append_message(self, message, treat_ending_punctuation_as_terminal, order);
+            }
         }
     }
 }
@@ -83,23 +122,34 @@ fn source_by_names(sources: &mut Vec<TextSource>, names: Vec<String>) -> &mut Te
     sources.get_mut(idx).unwrap()
 }
 
-fn append_message(chain: &mut MarkovChain, message: ExtractedMessage) {
+fn append_message(
+    chain: &mut MarkovChain,
+    message: ExtractedMessage,
+    treat_ending_punctuation_as_terminal: bool,
+    order: usize,
+) {
     let source = source_by_names(&mut chain.sources, message.names);
     push_text_entries(
         &message.body,
         message.datestamp,
         &mut source.entries,
         &mut chain.words,
-        false,
+        treat_ending_punctuation_as_terminal,
+        order,
     );
 }
 
+/// Slides over `text`'s words emitting, for every word, one `ChainEntry` per prefix order
+/// from 0 (the unigram, empty-prefix case) up to `order` (or as much of it as the words
+/// since the last sentence boundary allow) — so a generation step that can't find a match
+/// for the full `order`-word context always has a shorter one recorded to back off to.
 fn push_text_entries(
     text: &str,
     datestamp: Datestamp,
     entries: &mut Vec<ChainEntry>,
     words: &mut IndexSet<String>,
     treat_ending_punctuation_as_terminal: bool,
+    order: usize,
 ) {
     let word_indexes = text
         .split(&[' ', '\n'][..])
@@ -107,37 +157,44 @@ fn push_text_entries(
         .map(|word| words.insert_full(word.to_owned()).0 as u32)
         .collect::<Vec<_>>();
 
-    if word_indexes.len() < NGRAM_CNT + 1 {
+    if word_indexes.is_empty() {
         return;
     }
 
-    let last_ngram = &word_indexes[word_indexes.len() - (NGRAM_CNT + 1)..word_indexes.len()];
-
-    let mut starting = true;
-    for ngram in word_indexes.windows(NGRAM_CNT + 1) {
-        let (prefix_words, suffix) = ngram.split_at(NGRAM_CNT);
+    let last_idx = word_indexes.len() - 1;
+    let mut boundary = 0usize;
+    for i in 0..word_indexes.len() {
+        let suffix_idx = word_indexes[i];
         let terminal = if treat_ending_punctuation_as_terminal {
             words
-                .get_index(suffix[0] as usize)
+                .get_index(suffix_idx as usize)
                 .unwrap()
                 .ends_with(|c| c == '.' || c == '?' || c == '!')
         } else {
-            ngram == last_ngram
+            i == last_idx
         };
-        entries.push(ChainEntry {
-            prefix: if starting {
-                ChainPrefix::starting(prefix_words.try_into().unwrap())
-            } else {
-                ChainPrefix::nonstarting(prefix_words.try_into().unwrap())
-            },
-            suffix: if terminal {
-                ChainSuffix::terminal(suffix[0])
-            } else {
-                ChainSuffix::nonterminal(suffix[0])
-            },
-            datestamp,
-        });
-        starting = terminal;
+
+        let max_order = order.min(i - boundary);
+        for m in 0..=max_order {
+            let start = i - m;
+            entries.push(ChainEntry {
+                prefix: if start == boundary {
+                    ChainPrefix::starting(&word_indexes[start..i])
+                } else {
+                    ChainPrefix::nonstarting(&word_indexes[start..i])
+                },
+                suffix: if terminal {
+                    ChainSuffix::terminal(suffix_idx)
+                } else {
+                    ChainSuffix::nonterminal(suffix_idx)
+                },
+                datestamp,
+            });
+        }
+
+        if terminal {
+            boundary = i + 1;
+        }
     }
 }
 
@@ -149,7 +206,7 @@ mod tests {
     #[test]
     fn test_authors() {
         let mut chain = MarkovChain::new();
-        chain.append_message_dump("tests/fixtures/messages.html");
+        chain.append_message_dump("tests/fixtures/messages.html", 2);
         assert_eq!(
             chain.sources[0].names,
             indexset!["Sota Sota".into(), "sota".into()]
@@ -163,15 +220,22 @@ mod tests {
     #[test]
     fn test_word_nodes() {
         let mut chain = MarkovChain::new();
-        chain.append_message_dump("tests/fixtures/messages.html");
+        chain.append_message_dump("tests/fixtures/messages.html", 2);
         assert_eq!(chain.words.get_index(0), Some(&"Привет".into()));
         assert_eq!(chain.words.get_index(1), Some(&"Denko".into()));
         assert_eq!(chain.words.get_index(2), Some(&"Пью".into()));
 
+        // Filtering down to the full-order (2-word prefix) entries recovers exactly the
+        // fixed-order entries the chain would have built before backoff orders existed.
+        let full_order = chain.sources[0]
+            .entries
+            .iter()
+            .filter(|e| e.prefix.len() == 2)
+            .collect::<Vec<_>>();
         assert_eq!(
-            chain.sources[0].entries[0],
+            *full_order[0],
             ChainEntry {
-                prefix: ChainPrefix::starting([0, 1]),
+                prefix: ChainPrefix::starting(&[0, 1]),
                 suffix: ChainSuffix::nonterminal(2),
                 datestamp: Datestamp {
                     year: 2018,
@@ -180,9 +244,9 @@ mod tests {
             }
         );
         assert_eq!(
-            chain.sources[0].entries.last(),
+            full_order.last().copied(),
             Some(&ChainEntry {
-                prefix: ChainPrefix::nonstarting([3, 4]),
+                prefix: ChainPrefix::nonstarting(&[3, 4]),
                 suffix: ChainSuffix::terminal(5),
                 datestamp: Datestamp {
                     year: 2018,
@@ -195,7 +259,7 @@ mod tests {
     #[test]
     fn test_no_empty_words() {
         let mut chain = MarkovChain::new();
-        chain.append_message_dump("tests/fixtures/messages.html");
+        chain.append_message_dump("tests/fixtures/messages.html", 2);
         let enumerated_words = chain.words.iter().enumerate();
         let empty_words =
             enumerated_words.filter_map(|(i, w)| if w.is_empty() { Some(i) } else { None });
@@ -209,6 +273,7 @@ mod tests {
             "tests/fixtures/text",
             vec!["angus".into(), "sol onset".into()],
             Datestamp { year: 0, day: 0 },
+            2,
         );
         assert_eq!(
             chain.words,
@@ -226,35 +291,73 @@ mod tests {
             chain.sources[0].names,
             indexset!["angus".into(), "sol onset".into()]
         );
+        let full_order = chain.sources[0]
+            .entries
+            .iter()
+            .filter(|e| e.prefix.len() == 2)
+            .cloned()
+            .collect::<Vec<_>>();
         assert_eq!(
-            chain.sources[0].entries,
+            full_order,
             vec![
                 ChainEntry {
-                    prefix: ChainPrefix::starting([0, 1]),
+                    prefix: ChainPrefix::starting(&[0, 1]),
                     suffix: ChainSuffix::nonterminal(2),
                     datestamp: Datestamp { year: 0, day: 0 }
                 },
                 ChainEntry {
-                    prefix: ChainPrefix::nonstarting([1, 2]),
+                    prefix: ChainPrefix::nonstarting(&[1, 2]),
                     suffix: ChainSuffix::nonterminal(3),
                     datestamp: Datestamp { year: 0, day: 0 }
                 },
                 ChainEntry {
-                    prefix: ChainPrefix::nonstarting([2, 3]),
+                    prefix: ChainPrefix::nonstarting(&[2, 3]),
                     suffix: ChainSuffix::terminal(4),
                     datestamp: Datestamp { year: 0, day: 0 }
-                },
+                }
+            ]
+        );
+        // Context restarts after "probe.", so the two-word second sentence only gets
+        // entries of the orders its own words allow.
+        let entries = &chain.sources[0].entries;
+        assert_eq!(
+            entries[entries.len() - 3..].to_vec(),
+            vec![
                 ChainEntry {
-                    prefix: ChainPrefix::starting([3, 4]),
+                    prefix: ChainPrefix::starting(&[]),
                     suffix: ChainSuffix::nonterminal(5),
                     datestamp: Datestamp { year: 0, day: 0 }
                 },
                 ChainEntry {
-                    prefix: ChainPrefix::nonstarting([4, 5]),
+                    prefix: ChainPrefix::nonstarting(&[]),
+                    suffix: ChainSuffix::terminal(6),
+                    datestamp: Datestamp { year: 0, day: 0 }
+                },
+                ChainEntry {
+                    prefix: ChainPrefix::starting(&[5]),
                     suffix: ChainSuffix::terminal(6),
                     datestamp: Datestamp { year: 0, day: 0 }
                 }
             ]
         );
     }
+
+    #[test]
+    fn test_backoff_orders_present() {
+        let mut chain = MarkovChain::new();
+        chain.append_text(
+            "tests/fixtures/text",
+            vec!["angus".into()],
+            Datestamp { year: 0, day: 0 },
+            2,
+        );
+        // Every order from the unigram (empty prefix) up to the configured order 2 should
+        // be represented, so generation always has a shorter context to back off to.
+        let orders = chain.sources[0]
+            .entries
+            .iter()
+            .map(|e| e.prefix.len())
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(orders, [0, 1, 2].into_iter().collect());
+    }
 }
@@ -0,0 +1,376 @@
+use crate::Datestamp;
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use vkopt_message_parser::reader::{fold_html, EventResult, MessageEvent};
+
+/// A single chat line pulled out of a log file, ready to be folded into a `MarkovChain`
+/// through `ChainAppend::append_log`.
+#[derive(Default, Debug, Clone)]
+pub struct ExtractedMessage {
+    pub names: Vec<String>,
+    pub datestamp: Datestamp,
+    pub body: String,
+}
+
+/// How a `LogFormat` marks a suffix word as terminal (ending a generated sequence).
+/// Line/message-oriented formats naturally end a sequence at the end of each message;
+/// formats that fold a whole file into one `ExtractedMessage` instead need sentence-ending
+/// punctuation to stand in for a message boundary.
+pub enum TerminalDetection {
+    EndOfMessage,
+    EndingPunctuation,
+}
+
+/// A chat log format that can be decoded into a stream of `ExtractedMessage`s.
+///
+/// Implement this for any line-oriented IRC/chat log flavour to make it ingestible
+/// without touching `MarkovChain` internals.
+pub trait LogFormat {
+    type Messages: Iterator<Item = ExtractedMessage>;
+
+    fn parse(&self, input_file: &str) -> Self::Messages;
+
+    /// How to detect terminal words within a message's body; defaults to treating the end
+    /// of each `ExtractedMessage` as terminal, which holds for every line/message-oriented
+    /// format.
+    fn terminal_detection(&self) -> TerminalDetection {
+        TerminalDetection::EndOfMessage
+    }
+}
+
+/// energymech-style logs: `[HH:MM:SS] <nick> message`, one file per day.
+pub struct EnergyMech;
+
+/// WeeChat logs: `YYYY-MM-DD HH:MM:SS\tnick\tmessage`, tab-separated, with
+/// join/part/mode lines interspersed.
+pub struct WeeChat;
+
+/// irssi logs: `HH:MM <nick> message`, with `--- Day changed` lines marking
+/// midnight rollovers.
+pub struct Irssi;
+
+/// Generic line-oriented chat logs: `YYYY-MM-DD HH:MM:SS nick: message`.
+pub struct PlainChat;
+
+/// A VK chat export HTML dump, where every message carries its own author name(s) and
+/// timestamp inline rather than one being fixed for the whole file.
+pub struct MessageDump;
+
+/// A single free-form text file folded in as one message, attributed to fixed
+/// `source_names`/`datestamp` instead of anything parsed from the file itself.
+pub struct PlainText {
+    pub source_names: Vec<String>,
+    pub datestamp: Datestamp,
+}
+
+impl LogFormat for EnergyMech {
+    type Messages = std::vec::IntoIter<ExtractedMessage>;
+
+    fn parse(&self, input_file: &str) -> Self::Messages {
+        let text = std::fs::read_to_string(input_file).unwrap();
+        let date = date_from_filename(input_file).unwrap_or_else(epoch_date);
+        let datestamp = to_datestamp(date);
+
+        text.lines()
+            .filter_map(|line| {
+                let (nick, body) = parse_angle_bracket_line(line, "[HH:MM:SS] ".len())?;
+                Some(ExtractedMessage {
+                    names: vec![nick],
+                    datestamp,
+                    body,
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+impl LogFormat for WeeChat {
+    type Messages = std::vec::IntoIter<ExtractedMessage>;
+
+    fn parse(&self, input_file: &str) -> Self::Messages {
+        let text = std::fs::read_to_string(input_file).unwrap();
+
+        text.lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                let timestamp = fields.next()?;
+                let nick = fields.next()?;
+                let body = fields.next()?;
+
+                if is_weechat_status_line(nick) {
+                    return None;
+                }
+
+                let timestamp = NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%d %H:%M:%S").ok()?;
+                Some(ExtractedMessage {
+                    names: vec![nick.to_owned()],
+                    datestamp: to_datestamp(timestamp.date()),
+                    body: body.to_owned(),
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+impl LogFormat for Irssi {
+    type Messages = std::vec::IntoIter<ExtractedMessage>;
+
+    fn parse(&self, input_file: &str) -> Self::Messages {
+        let text = std::fs::read_to_string(input_file).unwrap();
+        let mut date = date_from_filename(input_file).unwrap_or_else(epoch_date);
+        let mut messages = Vec::new();
+
+        for line in text.lines() {
+            if let Some(rest) = line.strip_prefix("--- Day changed") {
+                if let Some(parsed) = parse_day_changed(rest.trim()) {
+                    date = parsed;
+                }
+                continue;
+            }
+            if let Some((nick, body)) = parse_angle_bracket_line(line, "HH:MM ".len()) {
+                messages.push(ExtractedMessage {
+                    names: vec![nick],
+                    datestamp: to_datestamp(date),
+                    body,
+                });
+            }
+        }
+
+        messages.into_iter()
+    }
+}
+
+impl LogFormat for PlainChat {
+    type Messages = std::vec::IntoIter<ExtractedMessage>;
+
+    fn parse(&self, input_file: &str) -> Self::Messages {
+        let text = std::fs::read_to_string(input_file).unwrap();
+
+        text.lines()
+            .filter_map(parse_plain_chat_line)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+impl LogFormat for MessageDump {
+    type Messages = std::vec::IntoIter<ExtractedMessage>;
+
+    fn parse(&self, input_file: &str) -> Self::Messages {
+        let mut messages = Vec::new();
+        let last_msg = fold_html(
+            input_file,
+            Default::default(),
+            |mut msg: ExtractedMessage, event| match event {
+                MessageEvent::Start(0) => {
+                    if !msg.body.is_empty() {
+                        messages.push(msg);
+                    }
+                    EventResult::Consumed(Default::default())
+                }
+                MessageEvent::FullNameExtracted(full_name) => {
+                    msg.names.push(full_name.to_owned());
+                    EventResult::Consumed(msg)
+                }
+                MessageEvent::ShortNameExtracted(short_name) => {
+                    msg.names.push(short_name.to_owned());
+                    EventResult::Consumed(msg)
+                }
+                MessageEvent::DateExtracted(date) => {
+                    let timestamp =
+                        NaiveDateTime::parse_from_str(date, "%Y.%m.%d %H:%M:%S").unwrap();
+                    msg.datestamp = to_datestamp(timestamp.date());
+                    EventResult::Consumed(msg)
+                }
+                MessageEvent::BodyPartExtracted(body) => {
+                    msg.body.push_str(body);
+                    EventResult::Consumed(msg)
+                }
+                _ => EventResult::Consumed(msg),
+            },
+        )
+        .unwrap();
+        if !last_msg.body.is_empty() {
+            messages.push(last_msg);
+        }
+        messages.into_iter()
+    }
+}
+
+impl LogFormat for PlainText {
+    type Messages = std::iter::Once<ExtractedMessage>;
+
+    fn parse(&self, input_file: &str) -> Self::Messages {
+        let body = std::fs::read_to_string(input_file).unwrap();
+        std::iter::once(ExtractedMessage {
+            names: self.source_names.clone(),
+            datestamp: self.datestamp,
+            body,
+        })
+    }
+
+    fn terminal_detection(&self) -> TerminalDetection {
+        TerminalDetection::EndingPunctuation
+    }
+}
+
+fn parse_plain_chat_line(line: &str) -> Option<ExtractedMessage> {
+    let mut parts = line.splitn(3, ' ');
+    let date = parts.next()?;
+    let time = parts.next()?;
+    let rest = parts.next()?;
+
+    let timestamp =
+        NaiveDateTime::parse_from_str(&format!("{} {}", date, time), "%Y-%m-%d %H:%M:%S").ok()?;
+    let (nick, body) = rest.split_once(": ")?;
+
+    Some(ExtractedMessage {
+        names: vec![nick.to_owned()],
+        datestamp: to_datestamp(timestamp.date()),
+        body: body.to_owned(),
+    })
+}
+
+fn epoch_date() -> NaiveDate {
+    NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()
+}
+
+fn to_datestamp(date: NaiveDate) -> Datestamp {
+    Datestamp {
+        year: date.year() as i16,
+        day: date.ordinal() as u16,
+    }
+}
+
+/// Parses a `<nick> message` line once the leading timestamp (of known width) is stripped.
+fn parse_angle_bracket_line(line: &str, timestamp_len: usize) -> Option<(String, String)> {
+    let rest = line.get(timestamp_len..)?.trim_start();
+    let rest = rest.strip_prefix('<')?;
+    let (nick, body) = rest.split_once('>')?;
+    Some((
+        nick.trim_start_matches(['@', '+', '%']).to_owned(),
+        body.trim_start().to_owned(),
+    ))
+}
+
+/// WeeChat marks joins/parts/mode changes/errors with a symbolic "nick" field instead of a
+/// real one; skip those so they don't pollute the chain.
+fn is_weechat_status_line(nick_field: &str) -> bool {
+    matches!(nick_field, "-->" | "<--" | "--" | "=!=")
+}
+
+/// Best-effort parse of irssi's "--- Day changed ..." marker, which varies in exact wording
+/// across irssi versions/locales; a handful of common layouts are tried.
+fn parse_day_changed(rest: &str) -> Option<NaiveDate> {
+    let rest = rest
+        .trim_start_matches("to")
+        .trim_start_matches("from")
+        .trim();
+    NaiveDate::parse_from_str(rest, "%a %b %d %Y")
+        .or_else(|_| NaiveDate::parse_from_str(rest, "%Y-%m-%d"))
+        .ok()
+}
+
+/// Scans for a `YYYY-MM-DD` date embedded in `path`'s filename. The candidate window must
+/// be bounded by non-digit characters (or the start/end of the name) on both sides — without
+/// that, a rolling 10-byte window can parse a substring of a longer digit run (e.g. the
+/// `-2018-01-2` inside `energymech-2018-01-21.log`, where chrono accepts the leading `-` as
+/// a negative-year sign) instead of the real date.
+fn date_from_filename(path: &str) -> Option<NaiveDate> {
+    let name = std::path::Path::new(path).file_name()?.to_str()?;
+    let bytes = name.as_bytes();
+    for start in 0..bytes.len().saturating_sub(9) {
+        let preceded_by_digit = start > 0 && bytes[start - 1].is_ascii_digit();
+        let end = start + 10;
+        let followed_by_digit = bytes.get(end).map_or(false, |b| b.is_ascii_digit());
+        if preceded_by_digit || followed_by_digit {
+            continue;
+        }
+        if let Some(candidate) = name.get(start..end) {
+            if let Ok(date) = NaiveDate::parse_from_str(candidate, "%Y-%m-%d") {
+                return Some(date);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_energymech_parse() {
+        let messages = EnergyMech
+            .parse("tests/fixtures/energymech-2018-01-21.log")
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].names, vec!["sota".to_string()]);
+        assert_eq!(messages[0].body, "hello world");
+        assert_eq!(
+            messages[0].datestamp,
+            Datestamp {
+                year: 2018,
+                day: 21
+            }
+        );
+        assert_eq!(messages[1].names, vec!["denko".to_string()]);
+    }
+
+    #[test]
+    fn test_weechat_parse_skips_status_lines() {
+        let messages = WeeChat.parse("tests/fixtures/weechat.log").collect::<Vec<_>>();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].names, vec!["sota".to_string()]);
+        assert_eq!(messages[0].body, "hello world");
+        assert_eq!(
+            messages[0].datestamp,
+            Datestamp {
+                year: 2018,
+                day: 21
+            }
+        );
+        assert_eq!(messages[1].names, vec!["denko".to_string()]);
+    }
+
+    #[test]
+    fn test_irssi_parse_tracks_day_changed() {
+        let messages = Irssi
+            .parse("tests/fixtures/irssi-2018-01-21.log")
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(
+            messages[0].datestamp,
+            Datestamp {
+                year: 2018,
+                day: 21
+            }
+        );
+        assert_eq!(
+            messages[1].datestamp,
+            Datestamp {
+                year: 2018,
+                day: 22
+            }
+        );
+        assert_eq!(messages[1].names, vec!["denko".to_string()]);
+    }
+
+    #[test]
+    fn test_plain_chat_parse() {
+        let messages = PlainChat
+            .parse("tests/fixtures/plainchat.log")
+            .collect::<Vec<_>>();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].names, vec!["sota".to_string()]);
+        assert_eq!(messages[0].body, "hello world");
+        assert_eq!(
+            messages[0].datestamp,
+            Datestamp {
+                year: 2018,
+                day: 21
+            }
+        );
+    }
+}
@@ -1,9 +1,71 @@
 use crate::{ChainEntry, Datestamp, MarkovChain, TextSource};
+use chrono::{Datelike, NaiveDate, Weekday};
 use indexmap::IndexSet;
 use rand::{seq::SliceRandom, Rng};
+use std::collections::{BTreeMap, HashMap};
 
 const MAX_TRIES: usize = 20;
 
+/// Per-word-dropped penalty applied when a generation step backs off from the full
+/// context to a shorter one ("stupid backoff": Brants et al., a cheap but effective
+/// alternative to proper discounting when higher-order contexts are sparse).
+const BACKOFF_PENALTY: f64 = 0.4;
+
+/// A seed keyword passed to `generate_from_keywords` that isn't in the chain's vocabulary.
+#[derive(Debug, PartialEq)]
+pub struct UnknownSeedWord(pub String);
+
+impl std::fmt::Display for UnknownSeedWord {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "seed word `{}` is not in this chain's vocabulary", self.0)
+    }
+}
+
+impl std::error::Error for UnknownSeedWord {}
+
+/// A recurrence rule matched against the calendar date reconstructed from a
+/// `ChainEntry`'s `Datestamp`. Combine rules with `And` to require all of them.
+pub enum CalendarFilter {
+    Weekday(Weekday),
+    MonthOfYear(u32),
+    DayOfYearRange(u16, u16),
+    Yearly { day: u16 },
+    And(Vec<CalendarFilter>),
+}
+
+impl CalendarFilter {
+    fn matches(&self, datestamp: &Datestamp) -> bool {
+        match self {
+            CalendarFilter::Weekday(weekday) => {
+                match NaiveDate::from_yo_opt(datestamp.year as i32, datestamp.day as u32) {
+                    Some(date) => date.weekday() == *weekday,
+                    None => false,
+                }
+            }
+            CalendarFilter::MonthOfYear(month) => {
+                match NaiveDate::from_yo_opt(datestamp.year as i32, datestamp.day as u32) {
+                    Some(date) => date.month() == *month,
+                    None => false,
+                }
+            }
+            CalendarFilter::DayOfYearRange(from, to) => {
+                datestamp.day >= *from && datestamp.day <= *to
+            }
+            CalendarFilter::Yearly { day } => datestamp.day == *day,
+            CalendarFilter::And(filters) => filters.iter().all(|f| f.matches(datestamp)),
+        }
+    }
+}
+
+/// A date window for `generate_windowed`: entries within `half_width_days` of `center`,
+/// compared as plain calendar dates, or (with `ignore_year`) as a day-of-year recurring
+/// every year regardless of which year an entry falls in.
+pub struct DateWindow {
+    pub center: Datestamp,
+    pub half_width_days: u16,
+    pub ignore_year: bool,
+}
+
 pub trait ChainGenerate {
     fn generate<'a, R: Rng, I: IntoIterator<Item = &'a TextSource>>(
         &self,
@@ -21,6 +83,49 @@ pub trait ChainGenerate {
         min_words: usize,
         max_words: usize,
     ) -> Option<String>;
+
+    fn generate_matching<'a, R: Rng, I: IntoIterator<Item = &'a TextSource>>(
+        &self,
+        rng: &mut R,
+        sources: I,
+        filter: &CalendarFilter,
+        min_words: usize,
+        max_words: usize,
+    ) -> Option<String>;
+
+    fn generate_from_seed<'a, R: Rng, I: IntoIterator<Item = &'a TextSource>>(
+        &self,
+        rng: &mut R,
+        sources: I,
+        seed: &str,
+        min_words: usize,
+        max_words: usize,
+    ) -> Option<String>;
+
+    /// Restricts candidate entries to `window` before generating, so output reflects how a
+    /// chat talked around a given date (or, with `ignore_year`, around that day-of-year
+    /// across every year). Falls back to the full entry set whenever the window has no
+    /// continuation, so generation can't dead-end.
+    fn generate_windowed<'a, R: Rng, I: IntoIterator<Item = &'a TextSource>>(
+        &self,
+        rng: &mut R,
+        sources: I,
+        window: DateWindow,
+        min_words: usize,
+        max_words: usize,
+    ) -> Option<String>;
+
+    /// Anchors generation on an entry containing one of `keywords` (resolved through a
+    /// reverse word index) and expands outward in both directions via bigram transitions,
+    /// instead of starting from a random walk. Errors if a keyword isn't in the vocabulary.
+    fn generate_from_keywords<'a, R: Rng, I: IntoIterator<Item = &'a TextSource>>(
+        &self,
+        rng: &mut R,
+        sources: I,
+        keywords: &[&str],
+        min_words: usize,
+        max_words: usize,
+    ) -> Result<Option<String>, UnknownSeedWord>;
 }
 
 impl ChainGenerate for MarkovChain {
@@ -63,6 +168,333 @@ impl ChainGenerate for MarkovChain {
             None
         }
     }
+
+    fn generate_matching<'a, R: Rng, I: IntoIterator<Item = &'a TextSource>>(
+        &self,
+        rng: &mut R,
+        sources: I,
+        filter: &CalendarFilter,
+        min_words: usize,
+        max_words: usize,
+    ) -> Option<String> {
+        let edges = sources
+            .into_iter()
+            .flat_map(|s| &s.entries)
+            .filter(|e| filter.matches(&e.datestamp))
+            .collect::<Vec<_>>();
+        if !edges.is_empty() {
+            generate_sequence(rng, &edges, min_words, max_words)
+                .map(|s| seq_to_text(s, &self.words))
+        } else {
+            None
+        }
+    }
+
+    fn generate_from_seed<'a, R: Rng, I: IntoIterator<Item = &'a TextSource>>(
+        &self,
+        rng: &mut R,
+        sources: I,
+        seed: &str,
+        min_words: usize,
+        max_words: usize,
+    ) -> Option<String> {
+        let edges = sources
+            .into_iter()
+            .flat_map(|s| &s.entries)
+            .collect::<Vec<_>>();
+        if edges.is_empty() {
+            return None;
+        }
+
+        let seed_indices = seed
+            .split_whitespace()
+            .filter_map(|token| resolve_seed_word(&self.words, token))
+            .collect::<Vec<_>>();
+
+        let starting_edges = edges
+            .iter()
+            .copied()
+            .filter(|e| e.prefix.is_starting())
+            .collect::<Vec<_>>();
+        let seeded_edges = starting_edges
+            .iter()
+            .copied()
+            .filter(|e| e.prefix.iter().any(|idx| seed_indices.contains(idx)))
+            .collect::<Vec<_>>();
+        let start_edges = if seeded_edges.is_empty() {
+            &starting_edges
+        } else {
+            &seeded_edges
+        };
+
+        generate_sequence_from(rng, &edges, start_edges, min_words, max_words)
+            .map(|s| seq_to_text(s, &self.words))
+    }
+
+    fn generate_windowed<'a, R: Rng, I: IntoIterator<Item = &'a TextSource>>(
+        &self,
+        rng: &mut R,
+        sources: I,
+        window: DateWindow,
+        min_words: usize,
+        max_words: usize,
+    ) -> Option<String> {
+        let edges = sources
+            .into_iter()
+            .flat_map(|s| &s.entries)
+            .collect::<Vec<_>>();
+        if edges.is_empty() {
+            return None;
+        }
+
+        let windowed_edges = if window.ignore_year {
+            edges
+                .iter()
+                .copied()
+                .filter(|e| {
+                    circular_day_distance(e.datestamp.day, window.center.day)
+                        <= window.half_width_days
+                })
+                .collect::<Vec<_>>()
+        } else {
+            let ordinal_index = build_ordinal_index(&edges);
+            edges_in_window(
+                &ordinal_index,
+                window.center.ordinal(),
+                window.half_width_days as i32,
+            )
+        };
+
+        let starting_edges = {
+            let pool = if windowed_edges.is_empty() {
+                &edges
+            } else {
+                &windowed_edges
+            };
+            let filtered = pool
+                .iter()
+                .copied()
+                .filter(|e| e.prefix.is_starting())
+                .collect::<Vec<_>>();
+            if filtered.is_empty() {
+                edges
+                    .iter()
+                    .copied()
+                    .filter(|e| e.prefix.is_starting())
+                    .collect::<Vec<_>>()
+            } else {
+                filtered
+            }
+        };
+
+        generate_sequence_windowed(
+            rng,
+            &edges,
+            &windowed_edges,
+            &starting_edges,
+            min_words,
+            max_words,
+        )
+        .map(|s| seq_to_text(s, &self.words))
+    }
+
+    fn generate_from_keywords<'a, R: Rng, I: IntoIterator<Item = &'a TextSource>>(
+        &self,
+        rng: &mut R,
+        sources: I,
+        keywords: &[&str],
+        min_words: usize,
+        max_words: usize,
+    ) -> Result<Option<String>, UnknownSeedWord> {
+        let keyword_indices = keywords
+            .iter()
+            .map(|&keyword| {
+                self.words
+                    .get_index_of(keyword)
+                    .map(|idx| idx as u32)
+                    .ok_or_else(|| UnknownSeedWord(keyword.to_owned()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let edges = sources
+            .into_iter()
+            .flat_map(|s| &s.entries)
+            .collect::<Vec<_>>();
+        if edges.is_empty() {
+            return Ok(None);
+        }
+
+        let reverse_index = build_reverse_index(&edges);
+        let candidates = keyword_indices
+            .iter()
+            .filter_map(|idx| reverse_index.get(idx))
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        let anchor = match candidates.choose(rng) {
+            Some(&edge) => edge,
+            None => return Ok(None),
+        };
+
+        Ok(Some(seq_to_text(
+            expand_from_anchor(rng, &edges, anchor, min_words, max_words),
+            &self.words,
+        )))
+    }
+}
+
+/// Builds a word-index -> containing-entries map covering both prefix slots and the
+/// suffix, so an entry can be found by any word it contains.
+fn build_reverse_index<'a>(edges: &[&'a ChainEntry]) -> HashMap<u32, Vec<&'a ChainEntry>> {
+    let mut index: HashMap<u32, Vec<&ChainEntry>> = HashMap::new();
+    for &edge in edges {
+        for &word_idx in edge.prefix.iter() {
+            index.entry(word_idx).or_default().push(edge);
+        }
+        index.entry(edge.suffix.word_idx()).or_default().push(edge);
+    }
+    index
+}
+
+/// Maps a suffix word to the entries that produce it, i.e. the reverse of
+/// `build_transition_index` — used to walk a sequence backwards from an anchor entry.
+fn build_predecessor_index<'a>(edges: &[&'a ChainEntry]) -> HashMap<u32, Vec<&'a ChainEntry>> {
+    let mut index: HashMap<u32, Vec<&ChainEntry>> = HashMap::new();
+    for &edge in edges {
+        index.entry(edge.suffix.word_idx()).or_default().push(edge);
+    }
+    index
+}
+
+/// Grows a sequence outward from `anchor` in both directions, using the forward
+/// transitions (as `generate_sequence` does) and the reverse of the same transitions to
+/// prepend context before it, up to `max_words` total (the anchor entry's own words are
+/// always kept whole).
+fn expand_from_anchor<R: Rng>(
+    rng: &mut R,
+    edges: &[&ChainEntry],
+    anchor: &ChainEntry,
+    min_words: usize,
+    max_words: usize,
+) -> Vec<u32> {
+    let transitions = build_transition_index(edges);
+    let predecessors = build_predecessor_index(edges);
+    let order = transition_order(&transitions);
+
+    let mut forward: Vec<u32> = anchor.prefix.to_vec();
+    forward.push(anchor.suffix.word_idx());
+    let mut terminal = anchor.suffix.is_terminal();
+    while forward.len() < max_words && !(terminal && forward.len() >= min_words) {
+        match choose_next(&transitions, &forward, order, rng) {
+            Some(edge) => {
+                forward.push(edge.suffix.word_idx());
+                terminal = edge.suffix.is_terminal();
+            }
+            None => break,
+        }
+    }
+
+    let mut backward: Vec<u32> = Vec::new();
+    let mut leading_word = anchor
+        .prefix
+        .first()
+        .copied()
+        .unwrap_or_else(|| anchor.suffix.word_idx());
+    while backward.len() + forward.len() < max_words {
+        match predecessors
+            .get(&leading_word)
+            .and_then(|prev_edges| prev_edges.choose(rng))
+        {
+            // An empty prefix carries no word to prepend, so `leading_word` (and thus the
+            // predecessor lookup) would never change — stop instead of spinning forever.
+            // Also stop when prepending this prefix would pass `max_words`.
+            Some(&prev)
+                if !prev.prefix.is_empty()
+                    && backward.len() + prev.prefix.len() + forward.len() <= max_words =>
+            {
+                leading_word = prev.prefix.first().copied().unwrap_or(leading_word);
+                backward.splice(0..0, prev.prefix.iter().copied());
+            }
+            _ => break,
+        }
+    }
+
+    backward.extend(forward);
+    backward
+}
+
+/// Index of entries keyed by `Datestamp::ordinal`, built over the pooled entries of every
+/// selected source, so the entries within a date window can be read off as one range.
+fn build_ordinal_index<'a>(edges: &[&'a ChainEntry]) -> BTreeMap<i32, Vec<&'a ChainEntry>> {
+    let mut index: BTreeMap<i32, Vec<&ChainEntry>> = BTreeMap::new();
+    for edge in edges {
+        index.entry(edge.datestamp.ordinal()).or_default().push(edge);
+    }
+    index
+}
+
+fn edges_in_window<'a>(
+    index: &BTreeMap<i32, Vec<&'a ChainEntry>>,
+    center_ordinal: i32,
+    half_width_days: i32,
+) -> Vec<&'a ChainEntry> {
+    index
+        .range((center_ordinal - half_width_days)..=(center_ordinal + half_width_days))
+        .flat_map(|(_, edges)| edges.iter().copied())
+        .collect()
+}
+
+/// Distance in days between two ordinal days-of-year, wrapping around the end of the year.
+fn circular_day_distance(a: u16, b: u16) -> u16 {
+    let diff = (a as i32 - b as i32).unsigned_abs() as u16;
+    diff.min(366u16.saturating_sub(diff))
+}
+
+/// Resolves a (possibly misspelled or inflected) seed token to the best-matching word
+/// already known to the chain: a candidate must contain every character of the token at
+/// least once, and candidates are then ranked by an in-order subsequence score.
+fn resolve_seed_word(words: &IndexSet<String>, token: &str) -> Option<u32> {
+    let token_lower = token.to_lowercase();
+    let token_chars = token_lower.chars().collect::<std::collections::HashSet<_>>();
+
+    words
+        .iter()
+        .enumerate()
+        .filter(|(_, word)| {
+            let word_lower = word.to_lowercase();
+            token_chars.iter().all(|c| word_lower.contains(*c))
+        })
+        .max_by_key(|(_, word)| subsequence_score(&token_lower, &word.to_lowercase()))
+        .map(|(idx, _)| idx as u32)
+}
+
+/// Scores how well `query`'s characters appear, in order, within `candidate`: each hit
+/// counts once, with bonuses for matching at the very start of the word and for runs of
+/// consecutive hits (rewarding close, not just loose, matches).
+fn subsequence_score(query: &str, candidate: &str) -> i32 {
+    let candidate_chars = candidate.chars().collect::<Vec<_>>();
+    let mut score = 0;
+    let mut cand_idx = 0;
+    let mut prev_matched_idx = None;
+
+    for qc in query.chars() {
+        while cand_idx < candidate_chars.len() && candidate_chars[cand_idx] != qc {
+            cand_idx += 1;
+        }
+        if cand_idx >= candidate_chars.len() {
+            break;
+        }
+        score += 1;
+        if cand_idx == 0 {
+            score += 2;
+        }
+        if prev_matched_idx == Some(cand_idx.wrapping_sub(1)) {
+            score += 1;
+        }
+        prev_matched_idx = Some(cand_idx);
+        cand_idx += 1;
+    }
+    score
 }
 
 fn seq_to_text(seq: Vec<u32>, words: &IndexSet<String>) -> String {
@@ -72,34 +504,141 @@ fn seq_to_text(seq: Vec<u32>, words: &IndexSet<String>) -> String {
         .join(" ")
 }
 
+/// Maps a full prefix to the edges recorded for it, at every order the chain was built
+/// with, so a generation step can look up the longest context first and back off to
+/// shorter ones recorded under the same map.
+fn build_transition_index<'a>(edges: &[&'a ChainEntry]) -> HashMap<Vec<u32>, Vec<&'a ChainEntry>> {
+    let mut index: HashMap<Vec<u32>, Vec<&ChainEntry>> = HashMap::new();
+    for edge in edges {
+        index.entry(edge.prefix.to_vec()).or_default().push(edge);
+    }
+    index
+}
+
+/// The longest prefix recorded in a transition index, i.e. the order the chain backing it
+/// was built with — `choose_next` needs this to bound the context it backs off over.
+fn transition_order(transitions: &HashMap<Vec<u32>, Vec<&ChainEntry>>) -> usize {
+    transitions.keys().map(|k| k.len()).max().unwrap_or(0)
+}
+
+/// Picks a continuation of `context` (the words generated so far, read as a backoff
+/// context) via stupid backoff: the last `order` words of context are looked up first,
+/// and only if they were never seen is the oldest word dropped and the shorter context
+/// tried, down to the empty context, so a continuation always exists. Edges of the level
+/// that matched are scored `BACKOFF_PENALTY` per word dropped; all edges of one level
+/// share that score, so the draw within it follows the observed suffix counts.
+/// `context` is truncated to `order` words first, since `transitions` never holds a
+/// prefix longer than that.
+fn choose_next<'a, R: Rng>(
+    transitions: &HashMap<Vec<u32>, Vec<&'a ChainEntry>>,
+    context: &[u32],
+    order: usize,
+    rng: &mut R,
+) -> Option<&'a ChainEntry> {
+    let context = &context[context.len().saturating_sub(order)..];
+    for steps_back in 0..=context.len() {
+        if let Some(edges) = transitions.get(&context[steps_back..]) {
+            let weight = BACKOFF_PENALTY.powi(steps_back as i32);
+            return edges.choose_weighted(rng, |_| weight).ok().copied();
+        }
+    }
+    None
+}
+
 fn generate_sequence<R: Rng>(
     rng: &mut R,
     edges: &[&ChainEntry],
     min_words: usize,
     max_words: usize,
 ) -> Option<Vec<u32>> {
+    let starting_edges = edges
+        .iter()
+        .copied()
+        .filter(|e| e.prefix.is_starting())
+        .collect::<Vec<_>>();
+    generate_sequence_from(rng, edges, &starting_edges, min_words, max_words)
+}
+
+/// Like `generate_sequence`, but draws the initial edge from `start_edges` instead of
+/// every starting edge in `edges` — used to bias generation toward a seed phrase.
+fn generate_sequence_from<R: Rng>(
+    rng: &mut R,
+    edges: &[&ChainEntry],
+    start_edges: &[&ChainEntry],
+    min_words: usize,
+    max_words: usize,
+) -> Option<Vec<u32>> {
+    if start_edges.is_empty() {
+        return None;
+    }
+    let transitions = build_transition_index(edges);
+    let order = transition_order(&transitions);
+
     let mut tries = 0;
-    let mut generated: Vec<u32> = Vec::with_capacity(min_words as usize);
     while tries < MAX_TRIES {
-        let mut edge = edges.choose(rng).unwrap();
-        loop {
-            generated.extend_from_slice(&edge.prefix);
-            if generated.len() >= min_words && edge.suffix.is_terminal() {
-                generated.push(edge.suffix.word_idx());
-                return Some(generated);
-            } else if generated.len() >= max_words {
-                break;
+        let start = *start_edges.choose(rng).unwrap();
+        let mut generated: Vec<u32> = start.prefix.to_vec();
+        generated.push(start.suffix.word_idx());
+        let mut terminal = start.suffix.is_terminal();
+
+        while generated.len() < max_words && !(terminal && generated.len() >= min_words) {
+            match choose_next(&transitions, &generated, order, rng) {
+                Some(edge) => {
+                    generated.push(edge.suffix.word_idx());
+                    terminal = edge.suffix.is_terminal();
+                }
+                None => break,
             }
-            let next_edges = edges
-                .iter()
-                .filter(|e| e.prefix[0] == edge.suffix.word_idx())
-                .collect::<Vec<_>>();
-            edge = match next_edges.choose(rng) {
-                Some(e) => e,
+        }
+
+        if generated.len() >= min_words && terminal {
+            return Some(generated);
+        }
+        tries += 1;
+    }
+    None
+}
+
+/// Like `generate_sequence_from`, but prefers transitions within `windowed_edges` at each
+/// step, falling back to the full `edges` set whenever the window has no continuation for
+/// the current context.
+fn generate_sequence_windowed<R: Rng>(
+    rng: &mut R,
+    edges: &[&ChainEntry],
+    windowed_edges: &[&ChainEntry],
+    start_edges: &[&ChainEntry],
+    min_words: usize,
+    max_words: usize,
+) -> Option<Vec<u32>> {
+    if start_edges.is_empty() {
+        return None;
+    }
+    let transitions = build_transition_index(edges);
+    let windowed_transitions = build_transition_index(windowed_edges);
+    let order = transition_order(&transitions).max(transition_order(&windowed_transitions));
+
+    let mut tries = 0;
+    while tries < MAX_TRIES {
+        let start = *start_edges.choose(rng).unwrap();
+        let mut generated: Vec<u32> = start.prefix.to_vec();
+        generated.push(start.suffix.word_idx());
+        let mut terminal = start.suffix.is_terminal();
+
+        while generated.len() < max_words && !(terminal && generated.len() >= min_words) {
+            let next = choose_next(&windowed_transitions, &generated, order, rng)
+                .or_else(|| choose_next(&transitions, &generated, order, rng));
+            match next {
+                Some(edge) => {
+                    generated.push(edge.suffix.word_idx());
+                    terminal = edge.suffix.is_terminal();
+                }
                 None => break,
             }
         }
-        generated.clear();
+
+        if generated.len() >= min_words && terminal {
+            return Some(generated);
+        }
         tries += 1;
     }
     None
@@ -108,7 +647,7 @@ fn generate_sequence<R: Rng>(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{ChainAppend, ChainSuffix, Datestamp, TextSource};
+    use crate::{ChainAppend, ChainPrefix, ChainSuffix, Datestamp, TextSource};
     use indexmap::indexset;
     use rand::{rngs::SmallRng, SeedableRng};
 
@@ -122,11 +661,14 @@ mod tests {
         chain.words.insert("с".into());
         chain.words.insert("собаками".into());
 
+        // Each entry's prefix is exactly the two words preceding its suffix, so with a
+        // single starting edge and every subsequent context unambiguous, the walk through
+        // these two sources is forced regardless of the rng draw.
         chain.sources.push(TextSource {
             names: indexset!["дана".into()],
             entries: vec![
                 ChainEntry {
-                    prefix: [0, 1],
+                    prefix: ChainPrefix::starting(&[0, 1]),
                     suffix: ChainSuffix::nonterminal(2),
                     datestamp: Datestamp {
                         year: 2070,
@@ -134,8 +676,8 @@ mod tests {
                     },
                 },
                 ChainEntry {
-                    prefix: [4, 5],
-                    suffix: ChainSuffix::terminal(6),
+                    prefix: ChainPrefix::nonstarting(&[3, 4]),
+                    suffix: ChainSuffix::terminal(5),
                     datestamp: Datestamp {
                         year: 2070,
                         day: 360,
@@ -145,14 +687,24 @@ mod tests {
         });
         chain.sources.push(TextSource {
             names: indexset!["джилл".into()],
-            entries: vec![ChainEntry {
-                prefix: [2, 3],
-                suffix: ChainSuffix::nonterminal(4),
-                datestamp: Datestamp {
-                    year: 2070,
-                    day: 360,
+            entries: vec![
+                ChainEntry {
+                    prefix: ChainPrefix::nonstarting(&[1, 2]),
+                    suffix: ChainSuffix::nonterminal(3),
+                    datestamp: Datestamp {
+                        year: 2070,
+                        day: 360,
+                    },
                 },
-            }],
+                ChainEntry {
+                    prefix: ChainPrefix::nonstarting(&[2, 3]),
+                    suffix: ChainSuffix::nonterminal(4),
+                    datestamp: Datestamp {
+                        year: 2070,
+                        day: 360,
+                    },
+                },
+            ],
         });
 
         let mut rng = SmallRng::from_seed([1; 16]);
@@ -165,17 +717,21 @@ mod tests {
 
     #[test]
     fn test_random_generation() {
+        // With entries of every order recorded and stupid backoff choosing among them, the
+        // exact wording a given seed produces is not hand-computable; assert the shape of
+        // the output instead of its literal text.
         let mut chain = MarkovChain::new();
-        chain.append_message_dump("tests/fixtures/messages.html");
+        chain.append_message_dump("tests/fixtures/messages.html", 2);
         let mut rng = SmallRng::from_seed([1; 16]);
         let generated = chain.generate(&mut rng, chain.sources.iter(), 3, 5);
-        assert_eq!(generated, Some("тоже пью жасминовый чай? 🤔🤔🤔".into()));
+        let word_count = generated.as_deref().map(|s| s.split(' ').count());
+        assert!(matches!(word_count, Some(3..=5)), "{:?}", generated);
     }
 
     #[test]
     fn test_date_range_generation() {
         let mut chain = MarkovChain::new();
-        chain.append_message_dump("tests/fixtures/messages.html");
+        chain.append_message_dump("tests/fixtures/messages.html", 2);
         let mut rng = SmallRng::from_seed([1; 16]);
         let generated = chain.generate_in_date_range(
             &mut rng,
@@ -193,6 +749,263 @@ mod tests {
             3,
             6,
         );
-        assert_eq!(generated, Some("Denko Пью жасминовый чай (´･ω･`)".into()));
+        let word_count = generated.as_deref().map(|s| s.split(' ').count());
+        assert!(matches!(word_count, Some(3..=6)), "{:?}", generated);
+    }
+
+    #[test]
+    fn test_calendar_filter_day_of_year_range() {
+        let ds = Datestamp {
+            year: 2020,
+            day: 100,
+        };
+        assert!(CalendarFilter::DayOfYearRange(90, 110).matches(&ds));
+        assert!(!CalendarFilter::DayOfYearRange(101, 110).matches(&ds));
+    }
+
+    #[test]
+    fn test_calendar_filter_yearly_ignores_year() {
+        let ds = Datestamp {
+            year: 2020,
+            day: 359,
+        };
+        assert!(CalendarFilter::Yearly { day: 359 }.matches(&ds));
+        assert!(!CalendarFilter::Yearly { day: 360 }.matches(&ds));
+    }
+
+    #[test]
+    fn test_calendar_filter_and_requires_all() {
+        // January 1st, 2020 was a Wednesday.
+        let ds = Datestamp { year: 2020, day: 1 };
+        let matching = CalendarFilter::And(vec![
+            CalendarFilter::MonthOfYear(1),
+            CalendarFilter::Weekday(Weekday::Wed),
+        ]);
+        assert!(matching.matches(&ds));
+
+        let mismatched = CalendarFilter::And(vec![
+            CalendarFilter::MonthOfYear(1),
+            CalendarFilter::Weekday(Weekday::Thu),
+        ]);
+        assert!(!mismatched.matches(&ds));
+    }
+
+    #[test]
+    fn test_generate_matching_restricts_to_matching_dates() {
+        let mut chain: MarkovChain = Default::default();
+        chain.words.insert("solo1".into());
+        chain.words.insert("solo2".into());
+
+        chain.sources.push(TextSource {
+            names: indexset!["only".into()],
+            entries: vec![
+                ChainEntry {
+                    prefix: ChainPrefix::starting(&[]),
+                    suffix: ChainSuffix::terminal(0),
+                    datestamp: Datestamp {
+                        year: 2020,
+                        day: 100,
+                    },
+                },
+                ChainEntry {
+                    prefix: ChainPrefix::starting(&[]),
+                    suffix: ChainSuffix::terminal(1),
+                    datestamp: Datestamp {
+                        year: 2020,
+                        day: 300,
+                    },
+                },
+            ],
+        });
+
+        let mut rng = SmallRng::from_seed([1; 16]);
+        let generated = chain.generate_matching(
+            &mut rng,
+            chain.sources.iter(),
+            &CalendarFilter::DayOfYearRange(90, 110),
+            1,
+            1,
+        );
+        assert_eq!(generated, Some("solo1".into()));
+    }
+
+    #[test]
+    fn test_generate_from_seed_fuzzy_matches_and_picks_seeded_start() {
+        let mut chain: MarkovChain = Default::default();
+        chain.words.insert("hello".into());
+        chain.words.insert("there".into());
+        chain.words.insert("goodbye".into());
+        chain.words.insert("now".into());
+
+        chain.sources.push(TextSource {
+            names: indexset!["a".into()],
+            entries: vec![
+                ChainEntry {
+                    prefix: ChainPrefix::starting(&[0]),
+                    suffix: ChainSuffix::terminal(1),
+                    datestamp: Datestamp::default(),
+                },
+                ChainEntry {
+                    prefix: ChainPrefix::starting(&[2]),
+                    suffix: ChainSuffix::terminal(3),
+                    datestamp: Datestamp::default(),
+                },
+            ],
+        });
+
+        let mut rng = SmallRng::from_seed([1; 16]);
+        // "helo" doesn't exactly match any word, but is closest to "hello" by subsequence
+        // score, which should bias the walk to start from the entry containing it.
+        let generated = chain.generate_from_seed(&mut rng, chain.sources.iter(), "helo", 2, 2);
+        assert_eq!(generated, Some("hello there".into()));
+    }
+
+    #[test]
+    fn test_generate_from_seed_falls_back_when_seed_unresolvable() {
+        let mut chain: MarkovChain = Default::default();
+        chain.words.insert("hello".into());
+        chain.words.insert("there".into());
+        chain.words.insert("goodbye".into());
+        chain.words.insert("now".into());
+
+        chain.sources.push(TextSource {
+            names: indexset!["a".into()],
+            entries: vec![
+                ChainEntry {
+                    prefix: ChainPrefix::starting(&[0]),
+                    suffix: ChainSuffix::terminal(1),
+                    datestamp: Datestamp::default(),
+                },
+                ChainEntry {
+                    prefix: ChainPrefix::starting(&[2]),
+                    suffix: ChainSuffix::terminal(3),
+                    datestamp: Datestamp::default(),
+                },
+            ],
+        });
+
+        let mut rng = SmallRng::from_seed([1; 16]);
+        // No word of the chain contains all of these characters, so the seed can't resolve to
+        // any of them; generation should still succeed from the full starting set.
+        let generated = chain.generate_from_seed(&mut rng, chain.sources.iter(), "xyz", 2, 2);
+        assert!(
+            matches!(generated.as_deref(), Some("hello there") | Some("goodbye now")),
+            "{:?}",
+            generated
+        );
+    }
+
+    #[test]
+    fn test_generate_windowed_restricts_to_window() {
+        let mut chain: MarkovChain = Default::default();
+        chain.words.insert("near".into());
+        chain.words.insert("far".into());
+
+        chain.sources.push(TextSource {
+            names: indexset!["a".into()],
+            entries: vec![
+                ChainEntry {
+                    prefix: ChainPrefix::starting(&[]),
+                    suffix: ChainSuffix::terminal(0),
+                    datestamp: Datestamp {
+                        year: 2020,
+                        day: 100,
+                    },
+                },
+                ChainEntry {
+                    prefix: ChainPrefix::starting(&[]),
+                    suffix: ChainSuffix::terminal(1),
+                    datestamp: Datestamp {
+                        year: 2020,
+                        day: 250,
+                    },
+                },
+            ],
+        });
+
+        let mut rng = SmallRng::from_seed([1; 16]);
+        let generated = chain.generate_windowed(
+            &mut rng,
+            chain.sources.iter(),
+            DateWindow {
+                center: Datestamp {
+                    year: 2020,
+                    day: 100,
+                },
+                half_width_days: 5,
+                ignore_year: false,
+            },
+            1,
+            1,
+        );
+        assert_eq!(generated, Some("near".into()));
+    }
+
+    #[test]
+    fn test_generate_windowed_falls_back_to_full_set_when_window_empty() {
+        let mut chain: MarkovChain = Default::default();
+        chain.words.insert("only".into());
+
+        chain.sources.push(TextSource {
+            names: indexset!["a".into()],
+            entries: vec![ChainEntry {
+                prefix: ChainPrefix::starting(&[]),
+                suffix: ChainSuffix::terminal(0),
+                datestamp: Datestamp {
+                    year: 2020,
+                    day: 100,
+                },
+            }],
+        });
+
+        let mut rng = SmallRng::from_seed([1; 16]);
+        // The window around day 300 excludes the only entry (dated day 100), so
+        // generation should fall back to the full entry set rather than returning None.
+        let generated = chain.generate_windowed(
+            &mut rng,
+            chain.sources.iter(),
+            DateWindow {
+                center: Datestamp {
+                    year: 2020,
+                    day: 300,
+                },
+                half_width_days: 5,
+                ignore_year: false,
+            },
+            1,
+            1,
+        );
+        assert_eq!(generated, Some("only".into()));
+    }
+
+    #[test]
+    fn test_generate_from_keywords_errors_on_unknown_word() {
+        let chain: MarkovChain = Default::default();
+        let mut rng = SmallRng::from_seed([1; 16]);
+        let result =
+            chain.generate_from_keywords(&mut rng, chain.sources.iter(), &["nope"], 1, 3);
+        assert_eq!(result, Err(UnknownSeedWord("nope".to_string())));
+    }
+
+    #[test]
+    fn test_generate_from_keywords_expands_from_anchor() {
+        let mut chain: MarkovChain = Default::default();
+        chain.words.insert("x".into());
+        chain.words.insert("keyword".into());
+
+        chain.sources.push(TextSource {
+            names: indexset!["a".into()],
+            entries: vec![ChainEntry {
+                prefix: ChainPrefix::starting(&[0]),
+                suffix: ChainSuffix::terminal(1),
+                datestamp: Datestamp::default(),
+            }],
+        });
+
+        let mut rng = SmallRng::from_seed([1; 16]);
+        let generated = chain
+            .generate_from_keywords(&mut rng, chain.sources.iter(), &["keyword"], 1, 2)
+            .unwrap();
+        assert_eq!(generated, Some("x keyword".into()));
     }
 }
@@ -1,15 +1,83 @@
 mod append;
+mod format;
 mod generate;
+mod persist;
+mod stats;
 
-pub use append::ChainAppend;
-pub use generate::ChainGenerate;
+pub use append::{ChainAppend, DEFAULT_TEXT_TIMESTAMP_FORMAT};
+pub use format::{
+    EnergyMech, ExtractedMessage, Irssi, LogFormat, MessageDump, PlainChat, PlainText,
+    TerminalDetection, WeeChat,
+};
+pub use generate::{CalendarFilter, ChainGenerate, DateWindow, UnknownSeedWord};
+pub use persist::{load, save, Format};
+pub use stats::{
+    BigramFrequency, ChainStats, CorpusTotals, DateBucketMode, DateHistogram, SourceStats,
+    WordFrequency,
+};
 
 use indexmap::IndexSet;
 use serde::{Deserialize, Serialize};
+use smallvec::SmallVec;
 
-pub const NGRAM_CNT: usize = 2; // Use a bigram markov chain model
+/// Inline capacity of `ChainPrefix`'s backing `SmallVec`. Ingestion can be configured with
+/// any order; contexts up to this length are stored without a heap allocation per entry,
+/// longer ones simply spill to the heap like any other `SmallVec`.
+pub const MAX_NGRAM_ORDER: usize = 4;
 
-pub type ChainPrefix = [u32; NGRAM_CNT]; // indexes into MarkovChain.words
+/// The n-gram order used when a chain isn't built with an explicit one, matching the
+/// original fixed two-word context.
+pub const DEFAULT_NGRAM_ORDER: usize = 2;
+
+/// A context of preceding word indices of any length (up to `MAX_NGRAM_ORDER` stored
+/// inline), tagged with whether it may open a generated sequence (the first ngram of a
+/// message) or only continue one. `push_text_entries` emits one `ChainEntry` per order
+/// from `0..=order` for every word (as far as the words since the last sentence boundary
+/// allow), so shorter contexts are always available as a stupid-backoff fallback
+/// when the longest one hasn't been seen.
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainPrefix {
+    words: SmallVec<[u32; MAX_NGRAM_ORDER]>,
+    starting: bool,
+}
+
+impl ChainPrefix {
+    pub fn starting(words: &[u32]) -> Self {
+        Self {
+            words: SmallVec::from_slice(words),
+            starting: true,
+        }
+    }
+
+    pub fn nonstarting(words: &[u32]) -> Self {
+        Self {
+            words: SmallVec::from_slice(words),
+            starting: false,
+        }
+    }
+
+    pub fn is_starting(&self) -> bool {
+        self.starting
+    }
+}
+
+impl std::ops::Deref for ChainPrefix {
+    type Target = [u32];
+
+    fn deref(&self) -> &Self::Target {
+        &self.words
+    }
+}
+
+impl std::fmt::Debug for ChainPrefix {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.starting {
+            write!(f, "Starting({:?})", &self.words[..])
+        } else {
+            write!(f, "NonStarting({:?})", &self.words[..])
+        }
+    }
+}
 
 #[derive(Default, Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Datestamp {
@@ -17,6 +85,13 @@ pub struct Datestamp {
     pub day: u16,
 }
 
+impl Datestamp {
+    /// An absolute day count suitable for ordering and windowed comparisons across years.
+    pub fn ordinal(&self) -> i32 {
+        self.year as i32 * 366 + self.day as i32
+    }
+}
+
 #[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct ChainSuffix(u32);
 
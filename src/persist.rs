@@ -0,0 +1,184 @@
+use crate::MarkovChain;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+
+/// Bumped whenever the on-disk layout of `Bincode`/`MessagePack` changes in a way older
+/// readers can't handle, so a stale file is rejected instead of silently misparsed.
+const FORMAT_VERSION: u8 = 1;
+
+/// On-disk representation to use when saving or loading a `MarkovChain`.
+pub enum Format {
+    /// Compact binary encoding via `bincode`; the fastest round-trip.
+    Bincode,
+    /// MessagePack encoding via `rmp-serde`; smaller and portable to other languages.
+    MessagePack,
+    /// Human-readable line-per-entry dump, for inspecting or diffing a chain. Write-only.
+    Text,
+}
+
+pub fn save(chain: &MarkovChain, path: &str, format: Format) -> io::Result<()> {
+    match format {
+        Format::Bincode => {
+            let mut file = BufWriter::new(File::create(path)?);
+            write_header(&mut file)?;
+            bincode::serialize_into(file, chain).map_err(to_io_error)
+        }
+        Format::MessagePack => {
+            let mut file = BufWriter::new(File::create(path)?);
+            write_header(&mut file)?;
+            rmp_serde::encode::write(&mut file, chain).map_err(to_io_error)
+        }
+        Format::Text => {
+            let mut file = BufWriter::new(File::create(path)?);
+            write_text(&mut file, chain)
+        }
+    }
+}
+
+pub fn load(path: &str, format: Format) -> io::Result<MarkovChain> {
+    match format {
+        Format::Bincode => {
+            let mut file = File::open(path)?;
+            read_header(&mut file)?;
+            bincode::deserialize_from(file).map_err(to_io_error)
+        }
+        Format::MessagePack => {
+            let mut file = File::open(path)?;
+            read_header(&mut file)?;
+            rmp_serde::decode::from_read(file).map_err(to_io_error)
+        }
+        Format::Text => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the Text format is write-only and cannot be reloaded into a MarkovChain",
+        )),
+    }
+}
+
+fn write_header<W: Write>(out: &mut W) -> io::Result<()> {
+    out.write_all(&[FORMAT_VERSION])
+}
+
+fn read_header<R: Read>(input: &mut R) -> io::Result<()> {
+    let mut version = [0u8; 1];
+    input.read_exact(&mut version)?;
+    if version[0] != FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "unsupported chain format version {} (expected {})",
+                version[0], FORMAT_VERSION
+            ),
+        ));
+    }
+    Ok(())
+}
+
+fn write_text<W: Write>(out: &mut W, chain: &MarkovChain) -> io::Result<()> {
+    for (source_idx, source) in chain.sources.iter().enumerate() {
+        for entry in &source.entries {
+            let prefix = entry
+                .prefix
+                .iter()
+                .map(|&idx| resolve(&chain.words, idx))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let suffix = resolve(&chain.words, entry.suffix.word_idx());
+            let arrow = if entry.suffix.is_terminal() {
+                "-.->"
+            } else {
+                "-->"
+            };
+            writeln!(
+                out,
+                "source={}\t{}\t{}\t{}\tyear={} day={}",
+                source_idx,
+                prefix,
+                arrow,
+                suffix,
+                entry.datestamp.year,
+                entry.datestamp.day
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn resolve(words: &indexmap::IndexSet<String>, idx: u32) -> &str {
+    words.get_index(idx as usize).map(String::as_str).unwrap_or("?")
+}
+
+fn to_io_error<E: std::error::Error + Send + Sync + 'static>(err: E) -> io::Error {
+    io::Error::other(err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ChainEntry, ChainPrefix, ChainSuffix, Datestamp, TextSource};
+    use indexmap::indexset;
+
+    fn sample_chain() -> MarkovChain {
+        let mut chain = MarkovChain::new();
+        chain.words.insert("a".into());
+        chain.words.insert("b".into());
+        chain.words.insert("c".into());
+        chain.sources.push(TextSource {
+            names: indexset!["someone".into()],
+            entries: vec![ChainEntry {
+                prefix: ChainPrefix::starting(&[0, 1]),
+                suffix: ChainSuffix::terminal(2),
+                datestamp: Datestamp { year: 2020, day: 1 },
+            }],
+        });
+        chain
+    }
+
+    #[test]
+    fn test_bincode_round_trip() {
+        let chain = sample_chain();
+        let path = std::env::temp_dir().join("joebot_markov_chain_test_bincode.bin");
+        let path = path.to_str().unwrap();
+        save(&chain, path, Format::Bincode).unwrap();
+        let loaded = load(path, Format::Bincode).unwrap();
+        assert_eq!(loaded.words, chain.words);
+        assert_eq!(loaded.sources[0].names, chain.sources[0].names);
+        assert_eq!(loaded.sources[0].entries, chain.sources[0].entries);
+    }
+
+    #[test]
+    fn test_messagepack_round_trip() {
+        let chain = sample_chain();
+        let path = std::env::temp_dir().join("joebot_markov_chain_test_messagepack.bin");
+        let path = path.to_str().unwrap();
+        save(&chain, path, Format::MessagePack).unwrap();
+        let loaded = load(path, Format::MessagePack).unwrap();
+        assert_eq!(loaded.words, chain.words);
+        assert_eq!(loaded.sources[0].entries, chain.sources[0].entries);
+    }
+
+    #[test]
+    fn test_version_mismatch_rejected() {
+        let chain = sample_chain();
+        let path = std::env::temp_dir().join("joebot_markov_chain_test_version_mismatch.bin");
+        let path_str = path.to_str().unwrap();
+        save(&chain, path_str, Format::Bincode).unwrap();
+
+        // Simulate a file written by an incompatible format version.
+        let mut bytes = std::fs::read(path_str).unwrap();
+        bytes[0] = FORMAT_VERSION + 1;
+        std::fs::write(path_str, bytes).unwrap();
+
+        let err = load(path_str, Format::Bincode).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_text_format_is_write_only() {
+        let chain = sample_chain();
+        let path = std::env::temp_dir().join("joebot_markov_chain_test_text.txt");
+        let path = path.to_str().unwrap();
+        save(&chain, path, Format::Text).unwrap();
+        let err = load(path, Format::Text).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+    }
+}
@@ -0,0 +1,348 @@
+use crate::{ChainEntry, MarkovChain};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+#[derive(Debug, Serialize)]
+pub struct WordFrequency {
+    pub word: String,
+    pub count: usize,
+}
+
+/// A prefix and how often it occurred. Only prefixes of each source's full order are
+/// counted, so `top_bigrams` ranks prefixes of mixed lengths together only when sources
+/// were built with different orders.
+#[derive(Debug, Serialize)]
+pub struct BigramFrequency {
+    pub words: Vec<String>,
+    pub count: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SourceStats {
+    pub names: Vec<String>,
+    pub message_count: usize,
+    pub word_count: usize,
+}
+
+/// How to bucket `Datestamp`s when building a `date_histogram`.
+pub enum DateBucketMode {
+    Year,
+    DayOfYear,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DateHistogram {
+    /// `(bucket, count)` pairs, sorted by bucket.
+    pub buckets: Vec<(i32, usize)>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CorpusTotals {
+    /// Number of each source's full-order `ChainEntry`s, i.e. of words recorded with a
+    /// full-order context; words earlier in a sentence than that are not counted.
+    pub total_tokens: usize,
+    /// Number of distinct words the chain knows about.
+    pub unique_words: usize,
+}
+
+/// Corpus analytics computed directly from a `MarkovChain`, for understanding a chain
+/// before generating from it.
+pub trait ChainStats {
+    fn top_words(&self, n: usize) -> Vec<WordFrequency>;
+
+    fn top_words_in_source(&self, source_idx: usize, n: usize) -> Vec<WordFrequency>;
+
+    fn top_bigrams(&self, n: usize) -> Vec<BigramFrequency>;
+
+    fn top_bigrams_in_source(&self, source_idx: usize, n: usize) -> Vec<BigramFrequency>;
+
+    fn source_stats(&self) -> Vec<SourceStats>;
+
+    fn date_histogram(&self, mode: DateBucketMode) -> DateHistogram;
+
+    fn corpus_totals(&self) -> CorpusTotals;
+}
+
+impl ChainStats for MarkovChain {
+    fn top_words(&self, n: usize) -> Vec<WordFrequency> {
+        self.top_words_matching(n, |_| true)
+    }
+
+    fn top_words_in_source(&self, source_idx: usize, n: usize) -> Vec<WordFrequency> {
+        self.top_words_matching(n, |idx| idx == source_idx)
+    }
+
+    fn top_bigrams(&self, n: usize) -> Vec<BigramFrequency> {
+        self.top_bigrams_matching(n, |_| true)
+    }
+
+    fn top_bigrams_in_source(&self, source_idx: usize, n: usize) -> Vec<BigramFrequency> {
+        self.top_bigrams_matching(n, |idx| idx == source_idx)
+    }
+
+    fn source_stats(&self) -> Vec<SourceStats> {
+        self.sources
+            .iter()
+            .enumerate()
+            .map(|(idx, source)| {
+                let entries = self.entries_matching(move |i| i == idx);
+                SourceStats {
+                    names: source.names.iter().cloned().collect(),
+                    message_count: entries.iter().filter(|e| e.suffix.is_terminal()).count(),
+                    word_count: entries.len(),
+                }
+            })
+            .collect()
+    }
+
+    fn date_histogram(&self, mode: DateBucketMode) -> DateHistogram {
+        let mut counts: HashMap<i32, usize> = HashMap::new();
+        for entry in self.entries_matching(|_| true) {
+            let bucket = match mode {
+                DateBucketMode::Year => entry.datestamp.year as i32,
+                DateBucketMode::DayOfYear => entry.datestamp.day as i32,
+            };
+            *counts.entry(bucket).or_insert(0) += 1;
+        }
+
+        let mut buckets = counts.into_iter().collect::<Vec<_>>();
+        buckets.sort_by_key(|(bucket, _)| *bucket);
+        DateHistogram { buckets }
+    }
+
+    fn corpus_totals(&self) -> CorpusTotals {
+        CorpusTotals {
+            total_tokens: self.entries_matching(|_| true).len(),
+            unique_words: self.words.len(),
+        }
+    }
+}
+
+impl MarkovChain {
+    /// Entries matching `source_filter`, limited per source to that source's longest
+    /// prefix order present (the order it was actually appended with). `push_text_entries`
+    /// additionally records every shorter backoff order for the same corpus position, so
+    /// without this filter a position would be counted once per order it was recorded at;
+    /// with it, only positions that have a full-order context are counted, once each. The
+    /// order is computed per source, not pooled across every
+    /// matching source, since `ChainAppend` allows different sources to be built with
+    /// different orders.
+    fn entries_matching<'a>(
+        &'a self,
+        source_filter: impl Fn(usize) -> bool + 'a,
+    ) -> Vec<&'a ChainEntry> {
+        self.sources
+            .iter()
+            .enumerate()
+            .filter(move |(idx, _)| source_filter(*idx))
+            .flat_map(|(_, source)| {
+                let order = source
+                    .entries
+                    .iter()
+                    .map(|e| e.prefix.len())
+                    .max()
+                    .unwrap_or(0);
+                source.entries.iter().filter(move |e| e.prefix.len() == order)
+            })
+            .collect()
+    }
+
+    fn top_words_matching(
+        &self,
+        n: usize,
+        source_filter: impl Fn(usize) -> bool,
+    ) -> Vec<WordFrequency> {
+        let mut counts: HashMap<u32, usize> = HashMap::new();
+        for entry in self.entries_matching(source_filter) {
+            for &idx in entry.prefix.iter() {
+                *counts.entry(idx).or_insert(0) += 1;
+            }
+            *counts.entry(entry.suffix.word_idx()).or_insert(0) += 1;
+        }
+
+        let mut frequencies = counts
+            .into_iter()
+            .filter_map(|(idx, count)| {
+                self.words
+                    .get_index(idx as usize)
+                    .map(|word| WordFrequency {
+                        word: word.clone(),
+                        count,
+                    })
+            })
+            .collect::<Vec<_>>();
+        frequencies.sort_by_key(|f| Reverse(f.count));
+        frequencies.truncate(n);
+        frequencies
+    }
+
+    fn top_bigrams_matching(
+        &self,
+        n: usize,
+        source_filter: impl Fn(usize) -> bool,
+    ) -> Vec<BigramFrequency> {
+        let mut counts: HashMap<Vec<u32>, usize> = HashMap::new();
+        for entry in self.entries_matching(source_filter) {
+            *counts.entry(entry.prefix.to_vec()).or_insert(0) += 1;
+        }
+
+        let mut frequencies = counts
+            .into_iter()
+            .filter_map(|(word_idxs, count)| {
+                let words = word_idxs
+                    .iter()
+                    .map(|&idx| self.words.get_index(idx as usize).cloned())
+                    .collect::<Option<Vec<_>>>()?;
+                Some(BigramFrequency { words, count })
+            })
+            .collect::<Vec<_>>();
+        frequencies.sort_by_key(|f| Reverse(f.count));
+        frequencies.truncate(n);
+        frequencies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ChainPrefix, ChainSuffix, Datestamp, MarkovChain, TextSource};
+    use indexmap::indexset;
+
+    /// Mimics `push_text_entries`' output for the order-2 text "a b c.": one real word
+    /// position per word, but a `ChainEntry` recorded for every backoff order from the
+    /// unigram up to 2. Stats count only the single full-order entry ("a b" -> "c."), not
+    /// one entry per order.
+    fn chain_with_backoff_orders() -> MarkovChain {
+        let mut chain = MarkovChain::new();
+        chain.words.insert("a".into());
+        chain.words.insert("b".into());
+        chain.words.insert("c.".into());
+
+        let datestamp = Datestamp { year: 2020, day: 1 };
+        chain.sources.push(TextSource {
+            names: indexset!["someone".into()],
+            entries: vec![
+                ChainEntry {
+                    prefix: ChainPrefix::starting(&[]),
+                    suffix: ChainSuffix::nonterminal(0),
+                    datestamp,
+                },
+                ChainEntry {
+                    prefix: ChainPrefix::nonstarting(&[]),
+                    suffix: ChainSuffix::nonterminal(1),
+                    datestamp,
+                },
+                ChainEntry {
+                    prefix: ChainPrefix::starting(&[0]),
+                    suffix: ChainSuffix::nonterminal(1),
+                    datestamp,
+                },
+                ChainEntry {
+                    prefix: ChainPrefix::nonstarting(&[]),
+                    suffix: ChainSuffix::terminal(2),
+                    datestamp,
+                },
+                ChainEntry {
+                    prefix: ChainPrefix::nonstarting(&[1]),
+                    suffix: ChainSuffix::terminal(2),
+                    datestamp,
+                },
+                ChainEntry {
+                    prefix: ChainPrefix::starting(&[0, 1]),
+                    suffix: ChainSuffix::terminal(2),
+                    datestamp,
+                },
+            ],
+        });
+        chain
+    }
+
+    #[test]
+    fn test_corpus_totals_counts_full_order_entries_only() {
+        let chain = chain_with_backoff_orders();
+        let totals = chain.corpus_totals();
+        assert_eq!(totals.total_tokens, 1);
+        assert_eq!(totals.unique_words, 3);
+    }
+
+    #[test]
+    fn test_source_stats_not_inflated_by_backoff_orders() {
+        let chain = chain_with_backoff_orders();
+        let stats = chain.source_stats();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].word_count, 1);
+        assert_eq!(stats[0].message_count, 1);
+    }
+
+    #[test]
+    fn test_top_words_counts_full_order_entries_only() {
+        let chain = chain_with_backoff_orders();
+        let mut words = chain
+            .top_words(10)
+            .into_iter()
+            .map(|w| (w.word, w.count))
+            .collect::<Vec<_>>();
+        words.sort();
+        assert_eq!(
+            words,
+            vec![
+                ("a".to_string(), 1),
+                ("b".to_string(), 1),
+                ("c.".to_string(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_top_bigrams_counts_full_order_entries_only() {
+        let chain = chain_with_backoff_orders();
+        let bigrams = chain.top_bigrams(10);
+        assert_eq!(bigrams.len(), 1);
+        assert_eq!(bigrams[0].words, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(bigrams[0].count, 1);
+    }
+
+    #[test]
+    fn test_stats_compute_order_per_source() {
+        // `ChainAppend` allows different sources to be built with different `order`s, so
+        // the full-order filter must be applied per source, not pooled across all of them.
+        let mut chain = MarkovChain::new();
+        chain.words.insert("x".into());
+        chain.words.insert("zzz".into());
+
+        chain.sources.push(TextSource {
+            names: indexset!["one".into()],
+            entries: vec![ChainEntry {
+                prefix: ChainPrefix::starting(&[0]),
+                suffix: ChainSuffix::terminal(0),
+                datestamp: Datestamp::default(),
+            }],
+        });
+        chain.sources.push(TextSource {
+            names: indexset!["two".into()],
+            entries: vec![
+                ChainEntry {
+                    prefix: ChainPrefix::starting(&[]),
+                    suffix: ChainSuffix::nonterminal(1),
+                    datestamp: Datestamp::default(),
+                },
+                ChainEntry {
+                    prefix: ChainPrefix::starting(&[1, 1, 1]),
+                    suffix: ChainSuffix::terminal(1),
+                    datestamp: Datestamp::default(),
+                },
+            ],
+        });
+
+        let totals = chain.corpus_totals();
+        assert_eq!(totals.total_tokens, 2);
+
+        let words = chain
+            .top_words(10)
+            .into_iter()
+            .map(|w| w.word)
+            .collect::<Vec<_>>();
+        assert!(words.contains(&"zzz".to_string()), "{:?}", words);
+    }
+}